@@ -8,10 +8,12 @@ use crate::shell_runtime::ShellRuntime;
 use crate::utils::{now, pretty_elapsed};
 use crate::vt100;
 use crate::xterm::Terminal;
-use chrono::Duration;
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::array_value_to_string;
+use chrono::{DateTime, Duration, Utc};
 use scopeguard::defer;
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Write;
 use std::sync::Arc;
 use std::sync::RwLock;
@@ -25,25 +27,232 @@ thread_local! {
 
 const HISTORY_LENGTH: usize = 1000;
 
+/// The name of the session that exists from the moment the shell starts
+const DEFAULT_SESSION: &str = "default";
+
 /// A shell input context
 #[wasm_bindgen]
 pub enum ShellInputContext {
     FileInput = 0,
 }
 
-/// Shell settings
+/// Per-session shell settings
 struct ShellSettings {
     /// Enable query timer
     timer: bool,
-    /// Is WebGL enabled?
-    webgl: bool,
+    /// The output format used to render query results
+    mode: OutputMode,
 }
 
 impl ShellSettings {
     fn default() -> Self {
         Self {
             timer: false,
-            webgl: false,
+            mode: OutputMode::Table,
+        }
+    }
+}
+
+/// The output format used to render query results
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    /// Box-drawn table (the default)
+    Table,
+    /// Comma-separated values
+    Csv,
+    /// A JSON array of row objects
+    Json,
+    /// A GitHub-flavored Markdown pipe table
+    Markdown,
+    /// Graphviz DOT for `EXPLAIN` statements; other results render as a table
+    Dot,
+}
+
+impl OutputMode {
+    /// The name as accepted/printed by `.mode`
+    fn name(&self) -> &'static str {
+        match self {
+            OutputMode::Table => "table",
+            OutputMode::Csv => "csv",
+            OutputMode::Json => "json",
+            OutputMode::Markdown => "markdown",
+            OutputMode::Dot => "dot",
+        }
+    }
+
+    /// Parse a mode name as accepted by `.mode <name>`
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "table" => Some(OutputMode::Table),
+            "csv" => Some(OutputMode::Csv),
+            "json" => Some(OutputMode::Json),
+            "markdown" | "md" => Some(OutputMode::Markdown),
+            "dot" => Some(OutputMode::Dot),
+            _ => None,
+        }
+    }
+}
+
+/// The outcome of a history entry
+enum HistoryStatus {
+    /// The command is still running
+    Running,
+    /// The command completed and returned a number of rows
+    Ok { rows: usize },
+    /// The command failed with an error message
+    Error { message: String },
+}
+
+/// A single entry in the query history
+struct HistoryEntry {
+    /// The raw command text (SQL statement or dot-command)
+    command: String,
+    /// The wall-clock time the command was issued, in milliseconds since the epoch
+    started_at: f64,
+    /// The measured elapsed time, updated once the command has finished
+    duration: Duration,
+    /// The outcome of the command
+    status: HistoryStatus,
+}
+
+impl HistoryEntry {
+    /// Serialize a history entry to a single persisted line
+    fn serialize(&self) -> String {
+        let (tag, detail) = match &self.status {
+            HistoryStatus::Running => ("running".to_string(), String::new()),
+            HistoryStatus::Ok { rows } => ("ok".to_string(), rows.to_string()),
+            HistoryStatus::Error { message } => ("error".to_string(), Self::escape_field(message)),
+        };
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.started_at,
+            self.duration.num_milliseconds(),
+            tag,
+            detail,
+            Self::escape_field(&self.command)
+        )
+    }
+
+    /// Escape newlines and tabs so a field cannot be mistaken for the `\t` field delimiter
+    /// or span multiple persisted lines
+    fn escape_field(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t")
+    }
+
+    /// Reverse `escape_field`
+    fn unescape_field(value: &str) -> String {
+        let mut out = String::with_capacity(value.len());
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('\\') => out.push('\\'),
+                    Some(other) => {
+                        out.push('\\');
+                        out.push(other);
+                    }
+                    None => out.push('\\'),
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    /// Parse a history entry from a persisted line
+    fn deserialize(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(5, '\t');
+        let started_at: f64 = parts.next()?.parse().ok()?;
+        let duration_ms: i64 = parts.next()?.parse().ok()?;
+        let tag = parts.next()?;
+        let detail = parts.next()?;
+        let command = Self::unescape_field(parts.next()?);
+        let status = match tag {
+            "ok" => HistoryStatus::Ok {
+                rows: detail.parse().unwrap_or(0),
+            },
+            "error" => HistoryStatus::Error {
+                message: Self::unescape_field(detail),
+            },
+            _ => HistoryStatus::Ok { rows: 0 },
+        };
+        Some(Self {
+            command,
+            started_at,
+            duration: Duration::milliseconds(duration_ms),
+            status,
+        })
+    }
+}
+
+/// State of an in-progress incremental reverse history search (Ctrl-R)
+struct HistorySearchState {
+    /// The substring being searched for
+    needle: String,
+    /// How many matches to skip back from the most recent one, cycled by repeated Ctrl-R
+    cursor: usize,
+    /// The input buffer text at the time the search started, restored on cancel
+    original_input: String,
+}
+
+impl HistorySearchState {
+    /// All history commands containing the needle, most recent first
+    fn matches<'a>(&self, history: &'a VecDeque<HistoryEntry>) -> Vec<&'a str> {
+        let needle = self.needle.to_lowercase();
+        history
+            .iter()
+            .rev()
+            .map(|entry| entry.command.as_str())
+            .filter(|cmd| cmd.to_lowercase().contains(&needle))
+            .collect()
+    }
+
+    /// The command currently selected by `cursor`, if any
+    fn current<'a>(&self, history: &'a VecDeque<HistoryEntry>) -> Option<&'a str> {
+        let matches = self.matches(history);
+        if matches.is_empty() {
+            None
+        } else {
+            Some(matches[self.cursor.min(matches.len() - 1)])
+        }
+    }
+}
+
+/// A single operator box recovered from DuckDB's box-drawn `EXPLAIN` output: its horizontal
+/// column span (used to recover parent/child nesting, since the plan text carries no
+/// indentation of its own) and the label extracted from inside the box
+struct PlanBox {
+    /// Column of the box's left (`┌`) border
+    left: usize,
+    /// Column of the box's right (`┐`) border
+    right: usize,
+    /// The operator name and detail lines found inside the box
+    label_lines: Vec<String>,
+}
+
+/// A single, independent working context: its own connection, history and settings,
+/// all running against the same underlying database
+struct Session {
+    /// The connection used by this session (set once attached to a database)
+    conn: Option<Arc<RwLock<AsyncDuckDBConnection>>>,
+    /// Session-local settings (timer, output mode)
+    settings: ShellSettings,
+    /// Session-local query history
+    history: VecDeque<HistoryEntry>,
+    /// The result batches of the most recently executed query in this session, used by `.copy`
+    last_result: Option<Vec<RecordBatch>>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            conn: None,
+            settings: ShellSettings::default(),
+            history: VecDeque::new(),
+            last_result: None,
         }
     }
 }
@@ -51,8 +260,8 @@ impl ShellSettings {
 /// The shell is the primary entrypoint for the Javascript api.
 /// It is stored as thread_local singleton and maintains all the state for the interactions with DuckDB
 pub struct Shell {
-    /// The shell settings
-    settings: ShellSettings,
+    /// Is WebGL enabled?
+    webgl: bool,
     /// The actual xterm terminal instance
     terminal: Terminal,
     /// The terminal width
@@ -65,38 +274,58 @@ pub struct Shell {
     input_enabled: bool,
     /// The input clock
     input_clock: u64,
-    /// This history buffer
-    history: VecDeque<String>,
-    /// The database (if any)
+    /// Active incremental reverse history search (Ctrl-R), if any
+    search: Option<HistorySearchState>,
+    /// The database (if any), shared by every session
     db: Option<Arc<RwLock<AsyncDuckDB>>>,
-    /// The connection (if any)
-    db_conn: Option<Arc<RwLock<AsyncDuckDBConnection>>>,
+    /// The named sessions, each with its own connection, history and settings
+    sessions: HashMap<String, Session>,
+    /// The name of the currently active session
+    current: String,
 }
 
 impl Shell {
     /// Construct a shell
     fn default() -> Self {
+        let mut sessions = HashMap::new();
+        sessions.insert(DEFAULT_SESSION.to_string(), Session::new());
         Self {
-            settings: ShellSettings::default(),
+            webgl: false,
             terminal: Terminal::construct(None),
             terminal_width: 100,
             runtime: None,
             input: PromptBuffer::default(),
             input_enabled: false,
             input_clock: 0,
-            history: VecDeque::new(),
+            search: None,
             db: None,
-            db_conn: None,
+            sessions,
+            current: DEFAULT_SESSION.to_string(),
         }
     }
 
+    /// The currently active session
+    fn session(&self) -> &Session {
+        self.sessions
+            .get(&self.current)
+            .expect("the current session always exists")
+    }
+
+    /// The currently active session, mutably
+    fn session_mut(&mut self) -> &mut Session {
+        self.sessions
+            .get_mut(&self.current)
+            .expect("the current session always exists")
+    }
+
     /// Attach to a terminal
     pub fn attach(&mut self, term: Terminal, runtime: ShellRuntime, options: ShellOptions) {
         self.terminal = term;
         self.terminal_width = self.terminal.get_cols() as usize;
         self.runtime = Some(runtime);
         self.input.configure(self.terminal_width);
-        self.settings.webgl = options.with_webgl();
+        self.webgl = options.with_webgl();
+        self.load_history();
 
         // Register on_key callback
         let callback = Closure::wrap(Box::new(move |e: web_sys::KeyboardEvent| {
@@ -112,13 +341,12 @@ impl Shell {
     pub async fn configure_database(db: AsyncDuckDB) -> Result<(), js_sys::Error> {
         // Teardown state (if there is any)
         let db = Shell::with_mut(|s| {
-            if s.db_conn.is_some() {
+            if s.session().conn.is_some() {
                 // XXX disconnect
                 return None;
             }
             // Store database
             let db = Arc::new(RwLock::new(db));
-            s.db_conn = None;
             s.db = Some(db.clone());
             Some(db)
         });
@@ -130,9 +358,9 @@ impl Shell {
         Shell::write_version_info().await;
         let conn = AsyncDuckDB::connect(db.unwrap().clone()).await?;
 
-        // Create connection
+        // Create connection for the default session
         Shell::with_mut(|s| {
-            s.db_conn = Some(Arc::new(RwLock::new(conn)));
+            s.session_mut().conn = Some(Arc::new(RwLock::new(conn)));
             s.write_connection_ready();
             s.prompt();
             s.focus();
@@ -195,6 +423,18 @@ impl Shell {
             .unwrap();
         };
 
+        let (mode, session_name) = Shell::with(|s| (s.session().settings.mode, s.current.clone()));
+        write!(
+            buffer,
+            "{crlf}{bold}Session:{normal} {session}{crlf}{bold}Output mode:{normal} {mode}{crlf}",
+            session = session_name,
+            mode = mode.name(),
+            bold = vt100::MODE_BOLD,
+            normal = vt100::MODES_OFF,
+            crlf = vt100::CRLF
+        )
+        .unwrap();
+
         let platform = platform::PlatformFeatures::get().await;
         write!(
             buffer,
@@ -316,17 +556,382 @@ impl Shell {
         }
     }
 
+    /// Render result batches as comma-separated values
+    fn format_csv(batches: &[RecordBatch]) -> String {
+        let mut out = String::new();
+        if let Some(first) = batches.first() {
+            let header: Vec<String> = first
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| Shell::csv_escape(f.name()))
+                .collect();
+            out.push_str(&header.join(","));
+            out.push_str(vt100::CRLF);
+        }
+        for batch in batches {
+            for row in 0..batch.num_rows() {
+                let cells: Vec<String> = (0..batch.num_columns())
+                    .map(|col| {
+                        Shell::csv_escape(
+                            &array_value_to_string(batch.column(col), row).unwrap_or_default(),
+                        )
+                    })
+                    .collect();
+                out.push_str(&cells.join(","));
+                out.push_str(vt100::CRLF);
+            }
+        }
+        out
+    }
+
+    /// Escape a single CSV value, quoting it if it contains a comma, quote or newline
+    fn csv_escape(value: &str) -> String {
+        if value.contains(',') || value.contains('"') || value.contains('\n') {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Render result batches as a JSON array of row objects keyed by column name
+    fn format_json(batches: &[RecordBatch]) -> String {
+        let mut rows = Vec::new();
+        for batch in batches {
+            let schema = batch.schema();
+            let names: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+            for row in 0..batch.num_rows() {
+                let mut obj = String::from("{");
+                for (col, name) in names.iter().enumerate() {
+                    if col > 0 {
+                        obj.push(',');
+                    }
+                    let value = array_value_to_string(batch.column(col), row).unwrap_or_default();
+                    write!(obj, "{}:{}", Shell::json_string(name), Shell::json_string(&value)).unwrap();
+                }
+                obj.push('}');
+                rows.push(obj);
+            }
+        }
+        format!("[{}]", rows.join(","))
+    }
+
+    /// Quote and escape a string for embedding in JSON output
+    fn json_string(value: &str) -> String {
+        let mut out = String::with_capacity(value.len() + 2);
+        out.push('"');
+        for c in value.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+        out
+    }
+
+    /// Render result batches as a GitHub-flavored Markdown pipe table
+    fn format_markdown(batches: &[RecordBatch]) -> String {
+        let mut out = String::new();
+        let first = match batches.first() {
+            Some(first) => first,
+            None => return out,
+        };
+        let names: Vec<String> = first
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        write!(out, "| {} |{crlf}", names.join(" | "), crlf = vt100::CRLF).unwrap();
+        write!(
+            out,
+            "| {} |{crlf}",
+            names.iter().map(|_| "---").collect::<Vec<_>>().join(" | "),
+            crlf = vt100::CRLF
+        )
+        .unwrap();
+        for batch in batches {
+            for row in 0..batch.num_rows() {
+                let cells: Vec<String> = (0..batch.num_columns())
+                    .map(|col| {
+                        let value = array_value_to_string(batch.column(col), row).unwrap_or_default();
+                        Shell::markdown_cell(&value)
+                    })
+                    .collect();
+                write!(out, "| {} |{crlf}", cells.join(" | "), crlf = vt100::CRLF).unwrap();
+            }
+        }
+        out
+    }
+
+    /// Escape a value for embedding in a Markdown pipe table cell: `|` would otherwise be read
+    /// as a column separator, and a literal newline would split one logical row across several
+    /// table lines
+    fn markdown_cell(value: &str) -> String {
+        let mut out = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '|' => out.push_str("\\|"),
+                '\n' => out.push_str("<br>"),
+                '\r' => {}
+                c => out.push(c),
+            }
+        }
+        out
+    }
+
+    /// Render result batches as tab-separated values
+    fn format_tsv(batches: &[RecordBatch]) -> String {
+        let mut out = String::new();
+        if let Some(first) = batches.first() {
+            let header: Vec<String> = first
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().clone())
+                .collect();
+            out.push_str(&header.join("\t"));
+            out.push_str(vt100::CRLF);
+        }
+        for batch in batches {
+            for row in 0..batch.num_rows() {
+                let cells: Vec<String> = (0..batch.num_columns())
+                    .map(|col| array_value_to_string(batch.column(col), row).unwrap_or_default())
+                    .collect();
+                out.push_str(&cells.join("\t"));
+                out.push_str(vt100::CRLF);
+            }
+        }
+        out
+    }
+
+    /// Render result batches in a given output mode, shared by `on_sql` and `.copy`
+    fn render_for_mode(mode: OutputMode, batches: &[RecordBatch], terminal_width: usize) -> String {
+        match mode {
+            OutputMode::Csv => Shell::format_csv(batches),
+            OutputMode::Json => Shell::format_json(batches),
+            OutputMode::Markdown => Shell::format_markdown(batches),
+            OutputMode::Table | OutputMode::Dot => {
+                pretty_format_batches(batches, terminal_width as u16, UTF8_BORDERS_NO_HORIZONTAL)
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// Run `EXPLAIN <sql>` and print the resulting plan as Graphviz DOT
+    pub async fn explain_command(sql: String) {
+        let sql = sql.trim();
+        if sql.is_empty() {
+            Shell::with(|s| s.writeln("Usage: .explain <sql>"));
+            return;
+        }
+        let maybe_conn = Shell::with(|s| s.session().conn.clone());
+        let conn = match maybe_conn {
+            Some(ref conn) => conn.read().unwrap(),
+            None => {
+                Shell::with(|s| s.writeln("Error: connection not set"));
+                return;
+            }
+        };
+        let query = format!("EXPLAIN {}", sql.trim_end_matches(';'));
+        let batches = match conn.run_query(&query).await {
+            Ok(batches) => batches,
+            Err(e) => {
+                let mut msg: String = e.message().into();
+                msg = msg.replace("\n", "\r\n");
+                Shell::with(|s| s.writeln(&format!("Error: {}", &msg)));
+                return;
+            }
+        };
+        let dot = Shell::render_plan_dot(&Shell::explain_text(&batches));
+        Shell::with(|s| s.writeln(&dot));
+    }
+
+    /// Extract the plan text from an `EXPLAIN` result (the last, most detailed column)
+    fn explain_text(batches: &[RecordBatch]) -> String {
+        let mut out = String::new();
+        for batch in batches {
+            if batch.num_columns() == 0 {
+                continue;
+            }
+            let col = batch.num_columns() - 1;
+            for row in 0..batch.num_rows() {
+                out.push_str(&array_value_to_string(batch.column(col), row).unwrap_or_default());
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// Parse DuckDB's box-drawing `EXPLAIN` output into operator boxes. DuckDB renders each
+    /// operator as a `┌─┐`/`│ │`/`└─┘` box, stacked top (root) to bottom (leaf scans), with
+    /// sibling operators (e.g. both sides of a join) drawn narrower and side by side under their
+    /// parent. There is no indentation to key off of, so structure is recovered from each box's
+    /// left/right column rather than from leading whitespace.
+    fn parse_explain_tree(text: &str) -> Vec<PlanBox> {
+        const BORDER_CHARS: &str = "┌┐└┘│─┬┴├┤┼";
+        // Index by character, not byte offset: box-drawing characters are multi-byte in UTF-8,
+        // so `str::find`/slicing by byte position would misalign columns across rows.
+        let rows: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+        let mut boxes = Vec::new();
+        let mut i = 0;
+        while i < rows.len() {
+            // A row can open more than one box side by side (e.g. both children of a JOIN),
+            // so find every `┌…┐` span on it rather than just the first and last.
+            let tops = Shell::find_box_spans(&rows[i], '┌', '┐');
+            if tops.is_empty() {
+                i += 1;
+                continue;
+            }
+
+            // Sibling boxes are padded to the same height, so they share one closing row
+            let mut j = i + 1;
+            while j < rows.len() && !Shell::row_closes_spans(&rows[j], &tops) {
+                j += 1;
+            }
+
+            for (left, right) in tops {
+                let mut label_lines = Vec::new();
+                for row in &rows[i + 1..j.min(rows.len())] {
+                    let content: String = row
+                        .iter()
+                        .skip(left)
+                        .take(right + 1 - left)
+                        .filter(|c| !BORDER_CHARS.contains(**c))
+                        .collect();
+                    let trimmed = content.trim();
+                    if !trimmed.is_empty() {
+                        label_lines.push(trimmed.to_string());
+                    }
+                }
+                boxes.push(PlanBox { left, right, label_lines });
+            }
+            i = j + 1;
+        }
+        boxes
+    }
+
+    /// Find every non-overlapping `open…close` span on a row, in left-to-right order
+    fn find_box_spans(row: &[char], open: char, close: char) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+        let mut col = 0;
+        while col < row.len() {
+            if row[col] == open {
+                match row[col..].iter().position(|&c| c == close) {
+                    Some(rel) => {
+                        let end = col + rel;
+                        spans.push((col, end));
+                        col = end + 1;
+                    }
+                    None => break,
+                }
+            } else {
+                col += 1;
+            }
+        }
+        spans
+    }
+
+    /// Whether a row carries a closing border (`└`) at the left column of every given span
+    fn row_closes_spans(row: &[char], spans: &[(usize, usize)]) -> bool {
+        spans.iter().all(|&(left, _)| row.get(left) == Some(&'└'))
+    }
+
+    /// Connect each box to its nearest ancestor whose column span encloses it, so every child
+    /// has exactly one parent
+    fn plan_edges(boxes: &[PlanBox]) -> Vec<(usize, usize)> {
+        let mut edges = Vec::new();
+        let mut stack: Vec<usize> = Vec::new();
+        for (id, b) in boxes.iter().enumerate() {
+            while let Some(&top) = stack.last() {
+                let ancestor = &boxes[top];
+                if ancestor.left <= b.left && b.right <= ancestor.right {
+                    break;
+                }
+                stack.pop();
+            }
+            if let Some(&parent) = stack.last() {
+                edges.push((parent, id));
+            }
+            stack.push(id);
+        }
+        edges
+    }
+
+    /// Render a parsed plan as a Graphviz `digraph`
+    fn render_plan_dot(text: &str) -> String {
+        let boxes = Shell::parse_explain_tree(text);
+        let edges = Shell::plan_edges(&boxes);
+
+        let mut out = String::new();
+        out.push_str("digraph plan {\r\n  rankdir=TB;\r\n");
+        for (id, b) in boxes.iter().enumerate() {
+            // Escape each line on its own, then join with a literal `\n` so Graphviz renders
+            // it as a line break rather than swallowing it into the backslash-escaping above
+            let label = b
+                .label_lines
+                .iter()
+                .map(|line| Shell::dot_escape(line))
+                .collect::<Vec<_>>()
+                .join("\\n");
+            write!(out, "  node_{id} [label=\"{label}\"];\r\n", id = id, label = label).unwrap();
+        }
+        for (parent, child) in &edges {
+            write!(out, "  node_{parent} -> node_{child};\r\n").unwrap();
+        }
+        out.push_str("}\r\n");
+        out
+    }
+
+    /// Escape a node label for embedding in a DOT string literal
+    fn dot_escape(s: &str) -> String {
+        s.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
     /// Command handler
     pub async fn on_command(text: String) {
         let trimmed = text.trim();
         Shell::with(|s| s.writeln("")); // XXX We could validate the input first and preserve the prompt
 
+        // Capture the session this entry belongs to up front: `.session new`/`.session switch`
+        // change the active session mid-command, and `s.current` can't be trusted afterward to
+        // still name the session this history entry was pushed into
+        let started_at = now();
+        let owner = Shell::with(|s| s.current.clone());
+        let idx = Shell::with_mut(|s| {
+            let session = s
+                .sessions
+                .get_mut(&owner)
+                .expect("the owning session always exists");
+            session.history.push_back(HistoryEntry {
+                command: text.clone(),
+                started_at,
+                duration: Duration::milliseconds(0),
+                status: HistoryStatus::Running,
+            });
+            session.history.len() - 1
+        });
+
         defer!({
             Shell::with_mut(|s| {
-                s.history.push_back(text.clone());
-                if s.history.len() > HISTORY_LENGTH {
-                    s.history.pop_front();
+                if let Some(session) = s.sessions.get_mut(&owner) {
+                    if let Some(entry) = session.history.get_mut(idx) {
+                        if matches!(entry.status, HistoryStatus::Running) {
+                            entry.status = HistoryStatus::Ok { rows: 0 };
+                        }
+                        entry.duration = Duration::milliseconds((now() - started_at) as i64);
+                    }
+                    if session.history.len() > HISTORY_LENGTH {
+                        session.history.pop_front();
+                    }
                 }
+                s.save_history_for(&owner);
                 s.writeln("");
                 s.prompt();
             })
@@ -346,18 +951,34 @@ impl Shell {
             }
             ".timer" => Shell::with_mut(|s| {
                 if args.ends_with("on") {
-                    s.settings.timer = true;
+                    s.session_mut().settings.timer = true;
                     s.writeln("Timer enabled");
                 } else if args.ends_with("off") {
-                    s.settings.timer = false;
+                    s.session_mut().settings.timer = false;
                     s.writeln("Timer disabled");
                 } else {
                     s.writeln("Usage: .timer [on/off]")
                 }
             }),
+            ".mode" => Shell::with_mut(|s| match OutputMode::parse(args) {
+                Some(mode) => {
+                    s.session_mut().settings.mode = mode;
+                    s.writeln(&format!("Output mode set to {}", mode.name()));
+                }
+                None => s.writeln("Usage: .mode [table/csv/json/markdown/dot]"),
+            }),
             ".fstats" => {
                 Shell::fstats_command(args.to_string()).await;
             }
+            ".explain" => {
+                Shell::explain_command(args.to_string()).await;
+            }
+            ".session" => {
+                Shell::session_command(args.to_string()).await;
+            }
+            ".copy" => {
+                Shell::copy_command(args.to_string()).await;
+            }
             ".files" => {
                 Shell::with_mut(|s| match s.runtime {
                     Some(ref rt) => {
@@ -369,18 +990,194 @@ impl Shell {
                 });
                 return;
             }
+            ".history" => {
+                Shell::history_command(args.to_string()).await;
+            }
             cmd => Shell::with(|s| s.writeln(&format!("Unknown command: {}", &cmd))),
         }
     }
 
+    /// Render or clear the query history of the active session
+    pub async fn history_command(args: String) {
+        if args.trim() == "clear" {
+            Shell::with_mut(|s| {
+                s.session_mut().history.clear();
+                s.save_history();
+                s.writeln("History cleared");
+            });
+            return;
+        }
+        Shell::with(|s| {
+            let table = Shell::render_history(&s.session().history);
+            s.writeln(&table);
+        });
+    }
+
+    /// Manage named sessions, each with its own connection, history and settings
+    pub async fn session_command(args: String) {
+        let mut parts = args.splitn(2, ' ');
+        let subcmd = parts.next().unwrap_or("").trim();
+        let name = parts.next().unwrap_or("").trim().to_string();
+
+        match subcmd {
+            "new" => {
+                if name.is_empty() {
+                    Shell::with(|s| s.writeln("Usage: .session new <name>"));
+                    return;
+                }
+                if Shell::with(|s| s.sessions.contains_key(&name)) {
+                    Shell::with(|s| s.writeln(&format!("Session already exists: {}", name)));
+                    return;
+                }
+                let db_ptr = Shell::with(|s| s.db.clone());
+                let db = match db_ptr {
+                    Some(db) => db,
+                    None => {
+                        Shell::with(|s| s.writeln("Error: database not set"));
+                        return;
+                    }
+                };
+                let conn = match AsyncDuckDB::connect(db).await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        Shell::with(|s| s.writeln(&format!("Error: {}", e.message())));
+                        return;
+                    }
+                };
+                Shell::with_mut(|s| {
+                    let mut session = Session::new();
+                    session.conn = Some(Arc::new(RwLock::new(conn)));
+                    s.sessions.insert(name.clone(), session);
+                    // Recover any history persisted under this name from a previous session
+                    s.load_history_for(&name);
+                    s.current = name.clone();
+                    s.writeln(&format!("Created and switched to session: {}", name));
+                });
+            }
+            "list" => Shell::with(|s| {
+                let mut names: Vec<&String> = s.sessions.keys().collect();
+                names.sort();
+                for session_name in names {
+                    let marker = if *session_name == s.current { "*" } else { " " };
+                    s.writeln(&format!("{} {}", marker, session_name));
+                }
+            }),
+            "switch" => {
+                if name.is_empty() {
+                    Shell::with(|s| s.writeln("Usage: .session switch <name>"));
+                    return;
+                }
+                Shell::with_mut(|s| {
+                    if s.sessions.contains_key(&name) {
+                        s.current = name.clone();
+                        s.writeln(&format!("Switched to session: {}", name));
+                    } else {
+                        s.writeln(&format!("Unknown session: {}", name));
+                    }
+                });
+            }
+            "close" => {
+                if name.is_empty() {
+                    Shell::with(|s| s.writeln("Usage: .session close <name>"));
+                    return;
+                }
+                Shell::with_mut(|s| {
+                    if name == DEFAULT_SESSION {
+                        s.writeln("Cannot close the default session");
+                    } else if s.current == name {
+                        s.writeln("Cannot close the active session");
+                    } else if s.sessions.remove(&name).is_some() {
+                        s.writeln(&format!("Closed session: {}", name));
+                    } else {
+                        s.writeln(&format!("Unknown session: {}", name));
+                    }
+                });
+            }
+            _ => Shell::with(|s| {
+                s.writeln("Usage: .session [new <name>|list|switch <name>|close <name>]")
+            }),
+        }
+    }
+
+    /// Copy the most recently rendered result to the system clipboard via an OSC 52 escape
+    /// sequence, serialized in the active output mode (or `tsv`/`csv`/`json` to override it
+    /// for this one copy)
+    pub async fn copy_command(args: String) {
+        let arg = args.trim().to_lowercase();
+        if !arg.is_empty() && arg != "tsv" && arg != "csv" && arg != "json" {
+            Shell::with(|s| s.writeln("Usage: .copy [tsv/csv/json]"));
+            return;
+        }
+        Shell::with(|s| {
+            let batches = match &s.session().last_result {
+                Some(batches) => batches,
+                None => {
+                    s.writeln("Error: no result to copy");
+                    return;
+                }
+            };
+            let serialized = match arg.as_str() {
+                "tsv" => Shell::format_tsv(batches),
+                "csv" => Shell::format_csv(batches),
+                "json" => Shell::format_json(batches),
+                _ => Shell::render_for_mode(s.session().settings.mode, batches, s.terminal_width),
+            };
+            s.write_osc52_clipboard(&serialized);
+            s.writeln("Copied result to clipboard");
+        });
+    }
+
+    /// Write an OSC 52 clipboard-set sequence for `payload` straight to the terminal
+    fn write_osc52_clipboard(&self, payload: &str) {
+        let encoded = Shell::base64_encode(payload.as_bytes());
+        self.terminal.write(&format!("\x1b]52;c;{}\x07", encoded));
+    }
+
+    /// A small, dependency-free base64 (RFC 4648 standard alphabet) encoder, used only to embed
+    /// `.copy` payloads in the OSC 52 escape sequence above
+    fn base64_encode(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+        for chunk in data.chunks(3) {
+            let b0 = chunk[0] as u32;
+            let b1 = *chunk.get(1).unwrap_or(&0) as u32;
+            let b2 = *chunk.get(2).unwrap_or(&0) as u32;
+            let n = (b0 << 16) | (b1 << 8) | b2;
+            out.push(ALPHABET[((n >> 18) & 0x3F) as usize] as char);
+            out.push(ALPHABET[((n >> 12) & 0x3F) as usize] as char);
+            out.push(if chunk.len() > 1 {
+                ALPHABET[((n >> 6) & 0x3F) as usize] as char
+            } else {
+                '='
+            });
+            out.push(if chunk.len() > 2 {
+                ALPHABET[(n & 0x3F) as usize] as char
+            } else {
+                '='
+            });
+        }
+        out
+    }
+
     /// Command handler
     async fn on_sql(text: String) {
+        let start = now();
+        let idx = Shell::with_mut(|s| {
+            s.session_mut().history.push_back(HistoryEntry {
+                command: text.clone(),
+                started_at: start,
+                duration: Duration::milliseconds(0),
+                status: HistoryStatus::Running,
+            });
+            s.session().history.len() - 1
+        });
+
         defer!({
             Shell::with_mut(|s| {
-                s.history.push_back(text.clone());
-                if s.history.len() > HISTORY_LENGTH {
-                    s.history.pop_front();
+                if s.session().history.len() > HISTORY_LENGTH {
+                    s.session_mut().history.pop_front();
                 }
+                s.save_history();
                 s.writeln("");
                 s.prompt();
             })
@@ -389,8 +1186,8 @@ impl Shell {
         let (maybe_conn, use_timer, terminal_width) = Shell::with(|shell| {
             shell.writeln("");
             (
-                shell.db_conn.clone(),
-                shell.settings.timer,
+                shell.session().conn.clone(),
+                shell.session().settings.timer,
                 shell.terminal_width,
             )
         });
@@ -401,13 +1198,18 @@ impl Shell {
             None => {
                 Shell::with_mut(|s| {
                     s.writeln("Error: connection not set");
+                    if let Some(entry) = s.session_mut().history.get_mut(idx) {
+                        entry.status = HistoryStatus::Error {
+                            message: "connection not set".to_string(),
+                        };
+                        entry.duration = Duration::milliseconds((now() - start) as i64);
+                    }
                 });
                 return;
             }
         };
 
         // Run the query
-        let start = now();
         let batches = match conn.run_query(&text).await {
             Ok(batches) => batches,
             Err(e) => {
@@ -415,26 +1217,36 @@ impl Shell {
                 msg = msg.replace("\n", "\r\n");
                 Shell::with_mut(|s| {
                     s.writeln(&format!("Error: {}{}", &msg, vt100::CRLF));
+                    if let Some(entry) = s.session_mut().history.get_mut(idx) {
+                        entry.status = HistoryStatus::Error { message: msg.clone() };
+                        entry.duration = Duration::milliseconds((now() - start) as i64);
+                    }
                 });
                 return;
             }
         };
-        let elapsed = if use_timer {
-            Duration::milliseconds((now() - start) as i64)
+        let elapsed = Duration::milliseconds((now() - start) as i64);
+        let row_count: usize = batches.iter().map(|b| b.num_rows()).sum();
+
+        // Render the result in the active output mode
+        let mode = Shell::with(|s| s.session().settings.mode);
+        let rendered = if mode == OutputMode::Dot && text.trim_start().to_lowercase().starts_with("explain")
+        {
+            Shell::render_plan_dot(&Shell::explain_text(&batches))
         } else {
-            Duration::milliseconds(0)
+            Shell::render_for_mode(mode, &batches, terminal_width)
         };
 
-        // Print the table
-        let pretty_table =
-            pretty_format_batches(&batches, terminal_width as u16, UTF8_BORDERS_NO_HORIZONTAL)
-                .unwrap_or_default();
-
         Shell::with_mut(|s| {
-            s.writeln(&pretty_table);
+            s.writeln(&rendered);
+            s.session_mut().last_result = Some(batches);
+            if let Some(entry) = s.session_mut().history.get_mut(idx) {
+                entry.status = HistoryStatus::Ok { rows: row_count };
+                entry.duration = elapsed;
+            }
 
             // Print elapsed time (if requested)
-            if s.settings.timer {
+            if use_timer {
                 s.writeln(&format!(
                     "{bold}Elapsed:{normal} {elapsed}",
                     elapsed = pretty_elapsed(&elapsed),
@@ -484,9 +1296,55 @@ impl Shell {
         if &event.type_() != "keydown" {
             return;
         }
+
+        let raw_key = event.key();
+        let ctrl = event.ctrl_key();
+
+        // Ctrl-Shift-C copies the most recent result to the clipboard via OSC 52. Route it
+        // through on_command (as if ".copy" had been entered) so it gets the same
+        // block_input/redraw treatment as every other command instead of splicing output
+        // straight into a still-live prompt. Cancel any in-progress Ctrl-R search first, since
+        // .copy's redraw would otherwise leave a stale search banner keystrokes kept feeding.
+        if ctrl && event.shift_key() && raw_key.eq_ignore_ascii_case("c") {
+            event.prevent_default();
+            Shell::with_mut(|s| {
+                s.cancel_history_search();
+                s.block_input();
+            });
+            spawn_local(Shell::on_command(".copy".to_string()));
+            return;
+        }
+
+        // Ctrl-R enters incremental reverse history search, or cycles to the next older match
+        if ctrl && raw_key.eq_ignore_ascii_case("r") {
+            event.prevent_default();
+            Shell::with_mut(|s| {
+                if s.search.is_some() {
+                    s.advance_history_search();
+                } else {
+                    s.start_history_search();
+                }
+                s.render_history_search();
+            });
+            return;
+        }
+
+        // Esc / Ctrl-C cancel an active search, restoring the original input
+        if Shell::with(|s| s.search.is_some())
+            && (raw_key == "Escape" || (ctrl && raw_key.eq_ignore_ascii_case("c")))
+        {
+            event.prevent_default();
+            Shell::with_mut(|s| s.cancel_history_search());
+            return;
+        }
+
         let event = KeyEvent::from_event(event);
         match event.key {
             Key::Enter => {
+                if Shell::with(|s| s.search.is_some()) {
+                    Shell::with_mut(|s| s.accept_history_search());
+                    return;
+                }
                 let input = Shell::with_mut(|s| {
                     s.input_clock += 1;
                     s.input.collect()
@@ -508,6 +1366,9 @@ impl Shell {
                     }
                 }
             }
+            Key::Backspace if Shell::with(|s| s.search.is_some()) => {
+                Shell::with_mut(|s| s.pop_history_search_char());
+            }
             Key::Backspace | Key::ArrowDown | Key::ArrowLeft | Key::ArrowRight | Key::ArrowUp => {
                 Shell::with_mut(|s| {
                     s.input_clock += 1;
@@ -516,6 +1377,13 @@ impl Shell {
                 });
             }
             _ => {
+                if Shell::with(|s| s.search.is_some()) {
+                    if raw_key.chars().count() == 1 {
+                        let c = raw_key.chars().next().unwrap();
+                        Shell::with_mut(|s| s.push_history_search_char(c));
+                    }
+                    return;
+                }
                 Shell::with_mut(|s| {
                     s.input_clock += 1;
                     s.input.consume(event);
@@ -577,6 +1445,164 @@ impl Shell {
         ));
     }
 
+    /// Render a history buffer as a table
+    fn render_history(history: &VecDeque<HistoryEntry>) -> String {
+        let mut buffer = String::new();
+        write!(
+            buffer,
+            "{bold}{:<4} {:<10} {:<8} {:<20} {}{normal}{crlf}",
+            "#",
+            "time",
+            "dur",
+            "status",
+            "sql",
+            bold = vt100::MODE_BOLD,
+            normal = vt100::MODES_OFF,
+            crlf = vt100::CRLF,
+        )
+        .unwrap();
+        for (i, entry) in history.iter().enumerate() {
+            let ts = DateTime::<Utc>::from_timestamp_millis(entry.started_at as i64)
+                .map(|d| d.format("%H:%M:%S").to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let status = match &entry.status {
+                HistoryStatus::Running => "running".to_string(),
+                HistoryStatus::Ok { rows } => format!("ok ({} rows)", rows),
+                HistoryStatus::Error { message } => format!("error: {}", message),
+            };
+            let sql = entry.command.replace('\n', " ");
+            let sql = if sql.chars().count() > 60 {
+                format!("{}...", sql.chars().take(60).collect::<String>())
+            } else {
+                sql
+            };
+            write!(
+                buffer,
+                "{:<4} {:<10} {:<8} {:<20} {}{crlf}",
+                i,
+                ts,
+                pretty_elapsed(&entry.duration),
+                status,
+                sql,
+                crlf = vt100::CRLF,
+            )
+            .unwrap();
+        }
+        buffer
+    }
+
+    /// Persist the active session's history through the shell runtime
+    fn save_history(&self) {
+        let current = self.current.clone();
+        self.save_history_for(&current);
+    }
+
+    /// Persist a named session's history through the shell runtime
+    fn save_history_for(&self, name: &str) {
+        if let Some(ref rt) = self.runtime {
+            if let Some(session) = self.sessions.get(name) {
+                let serialized = session
+                    .history
+                    .iter()
+                    .map(HistoryEntry::serialize)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                rt.save_history(name, &serialized);
+            }
+        }
+    }
+
+    /// Restore the active session's history from the shell runtime
+    fn load_history(&mut self) {
+        let current = self.current.clone();
+        self.load_history_for(&current);
+    }
+
+    /// Restore a named session's history from the shell runtime, if any was persisted for it
+    fn load_history_for(&mut self, name: &str) {
+        if let Some(ref rt) = self.runtime {
+            if let Some(serialized) = rt.load_history(name) {
+                if let Some(session) = self.sessions.get_mut(name) {
+                    session.history = serialized
+                        .lines()
+                        .filter_map(HistoryEntry::deserialize)
+                        .collect();
+                }
+            }
+        }
+    }
+
+    /// Start a new incremental reverse history search, remembering the input typed so far
+    fn start_history_search(&mut self) {
+        self.search = Some(HistorySearchState {
+            needle: String::new(),
+            cursor: 0,
+            original_input: self.input.collect(),
+        });
+    }
+
+    /// Cycle to the next (older) match for the current needle
+    fn advance_history_search(&mut self) {
+        let history = &self.sessions.get(&self.current).expect("the current session always exists").history;
+        if let Some(ref mut search) = self.search {
+            let count = search.matches(history).len();
+            if count > 0 {
+                search.cursor = (search.cursor + 1) % count;
+            }
+        }
+    }
+
+    /// Append a character to the search needle
+    fn push_history_search_char(&mut self, c: char) {
+        if let Some(ref mut search) = self.search {
+            search.needle.push(c);
+            search.cursor = 0;
+        }
+        self.render_history_search();
+    }
+
+    /// Remove the last character from the search needle
+    fn pop_history_search_char(&mut self) {
+        if let Some(ref mut search) = self.search {
+            search.needle.pop();
+            search.cursor = 0;
+        }
+        self.render_history_search();
+    }
+
+    /// Accept the current match into the prompt buffer and leave search mode
+    fn accept_history_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            let text = search
+                .current(&self.session().history)
+                .map(|m| m.to_string())
+                .unwrap_or(search.original_input);
+            self.input.set_text(&text);
+            self.flush();
+        }
+    }
+
+    /// Cancel an active search, restoring the input as it was before Ctrl-R was pressed
+    fn cancel_history_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.input.set_text(&search.original_input);
+            self.flush();
+        }
+    }
+
+    /// Render the `(reverse-i-search)` banner for the active search into the prompt buffer
+    fn render_history_search(&mut self) {
+        let banner = match &self.search {
+            Some(search) => match search.current(&self.session().history) {
+                Some(matched) => format!("(reverse-i-search)`{}': {}", search.needle, matched),
+                None => format!("(failed reverse-i-search)`{}': ", search.needle),
+            },
+            None => return,
+        };
+        self.input.set_text(&banner);
+        self.flush();
+    }
+
     /// Write the prompt
     pub fn prompt(&mut self) {
         self.input.start_new();
@@ -605,3 +1631,66 @@ impl Shell {
         SHELL.with(|s| f(&mut s.borrow_mut()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A real `EXPLAIN` result for `SELECT a FROM t WHERE a > 10`, as DuckDB renders it:
+    /// box-drawn operator boxes connected by a single-column vertical chain
+    const REAL_EXPLAIN: &str = "\
+┌───────────────────────────┐
+│    PROJECTION    │
+│   ────────────────────    │
+│             a              │
+└─────────────┬─────────────┘
+┌─────────────┴─────────────┐
+│           FILTER           │
+│   ────────────────────    │
+│          a > 10            │
+└─────────────┬─────────────┘
+┌─────────────┴─────────────┐
+│          SEQ_SCAN          │
+│   ────────────────────    │
+│              t              │
+└────────────────────────────┘";
+
+    #[test]
+    fn parses_box_drawn_explain_into_a_linear_chain() {
+        let boxes = Shell::parse_explain_tree(REAL_EXPLAIN);
+        assert_eq!(boxes.len(), 3);
+        assert_eq!(boxes[0].label_lines, vec!["PROJECTION".to_string(), "a".to_string()]);
+        assert_eq!(boxes[1].label_lines, vec!["FILTER".to_string(), "a > 10".to_string()]);
+        assert_eq!(boxes[2].label_lines, vec!["SEQ_SCAN".to_string(), "t".to_string()]);
+
+        let edges = Shell::plan_edges(&boxes);
+        assert_eq!(edges, vec![(0, 1), (1, 2)]);
+    }
+
+    /// A `HASH_JOIN` with two leaf scans drawn side by side on the same row, as DuckDB renders
+    /// any binary operator
+    const JOIN_EXPLAIN: &str = "\
+┌───────────────────────────┐
+│         HASH_JOIN         │
+│  ───────────────────────  │
+│           a = b           │
+└─────────────┬─────────────┘
+┌────────────┐ ┌────────────┐
+│   SCAN_A   │ │   SCAN_B   │
+│  ────────  │ │  ────────  │
+│     a      │ │     b      │
+└────────────┘ └────────────┘";
+
+    #[test]
+    fn parses_side_by_side_join_children_as_two_separate_nodes() {
+        let boxes = Shell::parse_explain_tree(JOIN_EXPLAIN);
+        assert_eq!(boxes.len(), 3);
+        assert_eq!(boxes[0].label_lines, vec!["HASH_JOIN".to_string(), "a = b".to_string()]);
+        assert_eq!(boxes[1].label_lines, vec!["SCAN_A".to_string(), "a".to_string()]);
+        assert_eq!(boxes[2].label_lines, vec!["SCAN_B".to_string(), "b".to_string()]);
+
+        // Both scans are children of the join, not of each other
+        let edges = Shell::plan_edges(&boxes);
+        assert_eq!(edges, vec![(0, 1), (0, 2)]);
+    }
+}